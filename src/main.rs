@@ -1,10 +1,16 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use std::{env, fs, io, process};
 
+use fnv::FnvBuildHasher;
 use sha2::{Digest, Sha256};
 
 use allsorts::binary::read::ReadScope;
+use allsorts::bitmap::{BitmapGlyph, EncapsulatedBitmap, EncapsulatedFormat};
+use allsorts::cmap::Cmap;
 use allsorts::fontfile::FontFile;
 use allsorts::tables::svg::SvgTable;
 use allsorts::tables::FontTableProvider;
@@ -15,16 +21,38 @@ use std::io::Read;
 const GZIP_HEADER: &[u8] = &[0x1F, 0x8B, 0x08];
 
 fn main() {
-    let args = env::args().collect::<Vec<_>>();
+    let mut args = env::args().collect::<Vec<_>>();
     if args.len() < 2 {
-        eprintln!("Usage: svg-dump path/to/SVGinOT.ttf [glyph id]");
+        eprintln!(
+            "Usage: svg-dump path/to/SVGinOT.ttf [glyph id | --char U+1F600 [selector]] [--png size out.png | --bitmap out] [--lenient]\n       svg-dump path/to/SVGinOT.ttf extract <dir>\n       svg-dump path/to/SVGinOT.ttf serve --port <port>"
+        );
         process::exit(2);
     }
 
-    let res = if let Some(glyph_id_arg) = args.get(2) {
-        dump_glyph(&args[1], glyph_id_arg)
-    } else {
-        hashes(&args[1])
+    let lenient = extract_flag(&mut args, "--lenient");
+
+    let res = match args.get(2).map(String::as_str) {
+        Some("extract") => match args.get(3) {
+            Some(dir) => extract(&args[1], dir.as_ref(), lenient),
+            None => Err(to_io_error("expected `extract <dir>`")),
+        },
+        Some("serve") => match parse_serve_port(&args[3..]) {
+            Ok(port) => serve(&args[1], port),
+            Err(err) => Err(to_io_error(err)),
+        },
+        Some("--char") => match parse_char_arg(&args[3..]) {
+            Ok((glyph, mode_args)) => match parse_output_mode(mode_args) {
+                Ok(mode) => dump_glyph(&args[1], glyph, mode, lenient),
+                Err(err) => Err(to_io_error(err)),
+            },
+            Err(err) => Err(to_io_error(err)),
+        },
+        Some(glyph_id_arg) => match (parse_glyph_id(glyph_id_arg), parse_output_mode(&args[3..])) {
+            (Ok(glyph), Ok(mode)) => dump_glyph(&args[1], glyph, mode, lenient),
+            (Err(err), _) => Err(to_io_error(err)),
+            (_, Err(err)) => Err(to_io_error(err)),
+        },
+        None => hashes(&args[1], lenient),
     };
 
     match res {
@@ -36,45 +64,298 @@ fn main() {
     }
 }
 
+fn extract_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    }
+}
+
 enum GlyphToDump {
     Id(u16),
     All,
+    Char { base: char, selector: Option<char> },
 }
 
-fn dump_glyph<P: AsRef<Path>>(path: P, glyph_id: &str) -> io::Result<()> {
-    let glyph_id = match glyph_id {
-        "all" => GlyphToDump::All,
-        _ => GlyphToDump::Id(glyph_id.parse().map_err(to_io_error)?),
-    };
+fn parse_glyph_id(arg: &str) -> Result<GlyphToDump, Box<dyn Error + Send + Sync>> {
+    match arg {
+        "all" => Ok(GlyphToDump::All),
+        _ => Ok(GlyphToDump::Id(arg.parse()?)),
+    }
+}
+
+fn parse_char_arg(
+    args: &[String],
+) -> Result<(GlyphToDump, &[String]), Box<dyn Error + Send + Sync>> {
+    let base = args
+        .first()
+        .ok_or("expected a character after --char, e.g. `--char U+1F600`")?;
+    let base = parse_char_spec(base)?;
+
+    match args.get(1) {
+        Some(maybe_selector) if looks_like_char_spec(maybe_selector) => {
+            let selector = parse_char_spec(maybe_selector)?;
+            Ok((
+                GlyphToDump::Char {
+                    base,
+                    selector: Some(selector),
+                },
+                &args[2..],
+            ))
+        }
+        _ => Ok((
+            GlyphToDump::Char {
+                base,
+                selector: None,
+            },
+            &args[1..],
+        )),
+    }
+}
+
+fn looks_like_char_spec(s: &str) -> bool {
+    s.starts_with("U+") || s.starts_with("u+") || s.chars().count() == 1
+}
+
+fn parse_char_spec(s: &str) -> Result<char, Box<dyn Error + Send + Sync>> {
+    if let Some(hex) = s.strip_prefix("U+").or_else(|| s.strip_prefix("u+")) {
+        let code_point = u32::from_str_radix(hex, 16)?;
+        return char::from_u32(code_point).ok_or_else(|| format!("U+{} is not a valid character", hex).into());
+    }
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("`{}` is not a single character or U+XXXX code point", s).into()),
+    }
+}
 
+enum OutputMode {
+    Xml,
+    Png { size: u32, destination: PathBuf },
+    Bitmap { destination: PathBuf },
+}
+
+fn parse_output_mode(args: &[String]) -> Result<OutputMode, Box<dyn Error + Send + Sync>> {
+    match args {
+        [] => Ok(OutputMode::Xml),
+        [flag, size, destination] if flag == "--png" => Ok(OutputMode::Png {
+            size: size.parse()?,
+            destination: PathBuf::from(destination),
+        }),
+        [flag, destination] if flag == "--bitmap" => Ok(OutputMode::Bitmap {
+            destination: PathBuf::from(destination),
+        }),
+        _ => Err("expected `--png <size> <out.png>` or `--bitmap <out>`".into()),
+    }
+}
+
+fn dump_glyph<P: AsRef<Path>>(
+    path: P,
+    glyph_id: GlyphToDump,
+    mode: OutputMode,
+    lenient: bool,
+) -> io::Result<()> {
     let buffer = fs::read(path)?;
     let scope = ReadScope::new(&buffer);
     let font_file = scope.read::<FontFile<'_>>().map_err(to_io_error)?;
     let table_provider = font_file.table_provider(0).map_err(to_io_error)?;
-    let svg_data = table_provider
-        .read_table_data(tag::SVG)
-        .map_err(to_io_error)?;
-    let svg = ReadScope::new(&svg_data).read::<SvgTable<'_>>().unwrap();
 
+    let glyph_id = match glyph_id {
+        GlyphToDump::Char { base, selector } => {
+            GlyphToDump::Id(lookup_glyph_id(&table_provider, base, selector)?)
+        }
+        glyph_id => glyph_id,
+    };
+
+    // Fonts encode color glyphs as either vector `SVG` documents or raster
+    // `sbix`/`CBDT` strikes; take the bitmap path when the caller asked for it
+    // via `--bitmap`, or when the SVG table is missing. The bitmap path only
+    // writes with `--bitmap <out>`; otherwise it returns an error.
+    let want_bitmap = matches!(mode, OutputMode::Bitmap { .. });
+    let svg_data = if want_bitmap {
+        None
+    } else {
+        table_provider.read_table_data(tag::SVG).ok()
+    };
+    let svg_data = match svg_data {
+        Some(svg_data) => svg_data,
+        None => return dump_bitmap_glyph(&table_provider, glyph_id, &mode),
+    };
+    let svg = ReadScope::new(&svg_data).read::<SvgTable<'_>>().map_err(to_io_error)?;
+
+    // `all` with `--png` writes one file per record, so the destination is
+    // always a directory, even if it doesn't exist yet.
+    if matches!(glyph_id, GlyphToDump::All) {
+        if let OutputMode::Png { destination, .. } = &mode {
+            fs::create_dir_all(destination)?;
+        }
+    }
+
+    let mut dumped = 0u32;
+    let mut skipped = 0u32;
     for record in svg.document_records.iter_res() {
-        let record = record.map_err(to_io_error)?;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) if lenient => {
+                eprintln!("svg-dump: skipping malformed document record: {}", err);
+                skipped += 1;
+                continue;
+            }
+            Err(err) => return Err(to_io_error(err)),
+        };
         match glyph_id {
             GlyphToDump::All => {
                 let svg_document = expand_document(record.svg_document)?;
-                println!("{}", svg_document);
+                emit(
+                    &svg_document,
+                    &mode,
+                    record.start_glyph_id,
+                    record.end_glyph_id,
+                    true,
+                )?;
+                dumped += 1;
             }
             GlyphToDump::Id(id) if id >= record.start_glyph_id && id <= record.end_glyph_id => {
                 let svg_document = expand_document(record.svg_document)?;
-                println!("{}", svg_document);
+                emit(
+                    &svg_document,
+                    &mode,
+                    record.start_glyph_id,
+                    record.end_glyph_id,
+                    false,
+                )?;
                 return Ok(());
             }
             _ => {}
         }
     }
 
+    // Only a whole-font dump can tell "nothing was usable" apart from "that
+    // glyph id just isn't covered by any record" — a single missing id isn't
+    // a skip, so don't report it as one.
+    if lenient && matches!(glyph_id, GlyphToDump::All) && dumped == 0 {
+        return Err(to_io_error(format!(
+            "no usable SVG documents found ({} record(s) skipped)",
+            skipped
+        )));
+    }
+
     Ok(())
 }
 
+fn emit(
+    svg_document: &str,
+    mode: &OutputMode,
+    start_glyph_id: u16,
+    end_glyph_id: u16,
+    is_all: bool,
+) -> io::Result<()> {
+    match mode {
+        OutputMode::Xml => {
+            println!("{}", svg_document);
+            Ok(())
+        }
+        OutputMode::Png { size, destination } => {
+            let png = render_to_png(svg_document, *size).map_err(to_io_error)?;
+            let destination = if is_all || destination.is_dir() {
+                destination.join(format!("{}-{}.png", start_glyph_id, end_glyph_id))
+            } else {
+                destination.clone()
+            };
+            fs::write(destination, png)
+        }
+        OutputMode::Bitmap { .. } => unreachable!("dump_glyph routes --bitmap to dump_bitmap_glyph"),
+    }
+}
+
+fn dump_bitmap_glyph(
+    table_provider: &impl FontTableProvider,
+    glyph_id: GlyphToDump,
+    mode: &OutputMode,
+) -> io::Result<()> {
+    let destination = match mode {
+        OutputMode::Bitmap { destination } => destination,
+        OutputMode::Xml | OutputMode::Png { .. } => {
+            return Err(to_io_error(
+                "this font has no SVG table; pass `--bitmap <out>` to dump the bitmap strike",
+            ))
+        }
+    };
+
+    let id = match glyph_id {
+        GlyphToDump::Id(id) => id,
+        GlyphToDump::All => {
+            return Err(to_io_error("`all` is not supported for bitmap glyphs, pass a glyph id"))
+        }
+        GlyphToDump::Char { .. } => unreachable!("dump_glyph resolves --char before dispatching"),
+    };
+
+    let glyph = BitmapGlyph::lookup(table_provider, id)
+        .map_err(to_io_error)?
+        .ok_or_else(|| to_io_error(format!("no bitmap strike found for glyph {}", id)))?;
+    let EncapsulatedBitmap { data, format } = glyph.bitmap;
+
+    let destination = if destination.is_dir() {
+        let ext = match format {
+            EncapsulatedFormat::Png => "png",
+            EncapsulatedFormat::Jpeg => "jpg",
+            EncapsulatedFormat::Tiff => "tiff",
+        };
+        destination.join(format!("{}.{}", id, ext))
+    } else {
+        destination.clone()
+    };
+
+    fs::write(destination, data)
+}
+
+fn lookup_glyph_id(
+    table_provider: &impl FontTableProvider,
+    base: char,
+    selector: Option<char>,
+) -> io::Result<u16> {
+    let cmap_data = table_provider
+        .read_table_data(tag::CMAP)
+        .map_err(to_io_error)?;
+    let cmap = ReadScope::new(&cmap_data).read::<Cmap<'_>>().map_err(to_io_error)?;
+    let subtable = cmap
+        .find_best_subtable()
+        .ok_or_else(|| to_io_error("font has no usable cmap subtable"))?;
+
+    if let Some(selector) = selector {
+        if let Some(id) = subtable
+            .map_variant(base as u32, selector as u32)
+            .map_err(to_io_error)?
+        {
+            return Ok(id);
+        }
+    }
+
+    subtable
+        .map_glyph(base as u32)
+        .map_err(to_io_error)?
+        .filter(|&id| id != 0)
+        .ok_or_else(|| to_io_error(format!("no glyph mapped for U+{:04X}", base as u32)))
+}
+
+fn render_to_png(svg_document: &str, size: u32) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let tree = usvg::Tree::from_str(svg_document, &usvg::Options::default())?;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).ok_or("requested PNG size is zero or too large")?;
+
+    let tree_size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        size as f32 / tree_size.width(),
+        size as f32 / tree_size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    pixmap.encode_png().map_err(|err| err.into())
+}
+
 fn expand_document(data: &[u8]) -> io::Result<String> {
     let doc = if data.starts_with(GZIP_HEADER) {
         let mut gz = GzDecoder::new(data);
@@ -88,7 +369,7 @@ fn expand_document(data: &[u8]) -> io::Result<String> {
     String::from_utf8(doc).map_err(to_io_error)
 }
 
-fn hashes<P: AsRef<Path>>(path: P) -> io::Result<()> {
+fn extract<P: AsRef<Path>>(path: P, dir: &Path, lenient: bool) -> io::Result<()> {
     let buffer = fs::read(path)?;
     let scope = ReadScope::new(&buffer);
     let font_file = scope.read::<FontFile<'_>>().map_err(to_io_error)?;
@@ -96,11 +377,74 @@ fn hashes<P: AsRef<Path>>(path: P) -> io::Result<()> {
     let svg_data = table_provider
         .read_table_data(tag::SVG)
         .map_err(to_io_error)?;
-    let svg = ReadScope::new(&svg_data).read::<SvgTable<'_>>().unwrap();
+    let svg = ReadScope::new(&svg_data)
+        .read::<SvgTable<'_>>()
+        .map_err(to_io_error)?;
+
+    fs::create_dir_all(dir)?;
+
+    let mut written = HashSet::new();
+    let mut manifest = String::new();
+    let mut dumped = 0u32;
+    let mut skipped = 0u32;
+    for record in svg.document_records.iter_res() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) if lenient => {
+                eprintln!("svg-dump: skipping malformed document record: {}", err);
+                skipped += 1;
+                continue;
+            }
+            Err(err) => return Err(to_io_error(err)),
+        };
+        let svg_document = expand_document(record.svg_document)?;
+        let hash = hexify(&Sha256::digest(svg_document.as_bytes()));
+
+        if written.insert(hash.clone()) {
+            fs::write(dir.join(format!("{}.svg", hash)), &svg_document)?;
+        }
+        manifest.push_str(&format!(
+            "{}\t{}\t{}\n",
+            record.start_glyph_id, record.end_glyph_id, hash
+        ));
+        dumped += 1;
+    }
+
+    if lenient && dumped == 0 {
+        return Err(to_io_error(format!(
+            "no usable SVG documents found ({} record(s) skipped)",
+            skipped
+        )));
+    }
+
+    fs::write(dir.join("manifest.tsv"), manifest)
+}
+
+fn hashes<P: AsRef<Path>>(path: P, lenient: bool) -> io::Result<()> {
+    let buffer = fs::read(path)?;
+    let scope = ReadScope::new(&buffer);
+    let font_file = scope.read::<FontFile<'_>>().map_err(to_io_error)?;
+    let table_provider = font_file.table_provider(0).map_err(to_io_error)?;
+    let svg_data = table_provider
+        .read_table_data(tag::SVG)
+        .map_err(to_io_error)?;
+    let svg = ReadScope::new(&svg_data)
+        .read::<SvgTable<'_>>()
+        .map_err(to_io_error)?;
 
     let mut hasher = Sha256::new();
+    let mut dumped = 0u32;
+    let mut skipped = 0u32;
     for record in svg.document_records.iter_res() {
-        let record = record.map_err(to_io_error)?;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) if lenient => {
+                eprintln!("svg-dump: skipping malformed document record: {}", err);
+                skipped += 1;
+                continue;
+            }
+            Err(err) => return Err(to_io_error(err)),
+        };
         hasher.update(record.svg_document);
         let hash = hasher.finalize_reset();
         println!(
@@ -109,11 +453,209 @@ fn hashes<P: AsRef<Path>>(path: P) -> io::Result<()> {
             record.end_glyph_id,
             hexify(&hash)
         );
+        dumped += 1;
+    }
+
+    if lenient && dumped == 0 {
+        return Err(to_io_error(format!(
+            "no usable SVG documents found ({} record(s) skipped)",
+            skipped
+        )));
+    }
+
+    Ok(())
+}
+
+fn parse_serve_port(args: &[String]) -> Result<u16, Box<dyn Error + Send + Sync>> {
+    match args {
+        [flag, port] if flag == "--port" => Ok(port.parse()?),
+        _ => Err("expected `--port <port>`".into()),
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct CacheKey {
+    glyph_id: u16,
+    size: u32,
+}
+
+struct PngCache {
+    capacity: usize,
+    order: VecDeque<CacheKey>,
+    entries: HashMap<CacheKey, Vec<u8>, FnvBuildHasher>,
+}
+
+impl PngCache {
+    fn new(capacity: usize) -> Self {
+        PngCache {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            entries: HashMap::with_capacity_and_hasher(capacity, FnvBuildHasher::default()),
+        }
+    }
+
+    fn get_or_render(
+        &mut self,
+        key: CacheKey,
+        render: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> io::Result<(Vec<u8>, bool)> {
+        match self.entries.entry(key) {
+            Entry::Occupied(entry) => {
+                let png = entry.get().clone();
+                self.touch(key);
+                Ok((png, true))
+            }
+            Entry::Vacant(entry) => {
+                let png = render()?;
+                entry.insert(png.clone());
+                self.order.push_back(key);
+                if self.order.len() > self.capacity {
+                    if let Some(oldest) = self.order.pop_front() {
+                        self.entries.remove(&oldest);
+                    }
+                }
+                Ok((png, false))
+            }
+        }
+    }
+
+    fn touch(&mut self, key: CacheKey) {
+        if let Some(pos) = self.order.iter().position(|k| *k == key) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(key);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum GlyphRequest {
+    Svg { glyph_id: u16 },
+    Png { glyph_id: u16, size: u32 },
+}
+
+fn parse_glyph_request(url: &str) -> Option<GlyphRequest> {
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    };
+    let rest = path.strip_prefix("/glyph/")?;
+
+    if let Some(id) = rest.strip_suffix(".svg") {
+        return Some(GlyphRequest::Svg {
+            glyph_id: id.parse().ok()?,
+        });
+    }
+
+    let id = rest.strip_suffix(".png")?;
+    let size = query
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("size=")))
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(128);
+    Some(GlyphRequest::Png {
+        glyph_id: id.parse().ok()?,
+        size,
+    })
+}
+
+fn find_record<'f>(
+    svg: &SvgTable<'f>,
+    glyph_id: u16,
+) -> Option<allsorts::tables::svg::SvgDocumentRecord<'f>> {
+    svg.document_records
+        .iter_res()
+        .filter_map(Result::ok)
+        .find(|record| glyph_id >= record.start_glyph_id && glyph_id <= record.end_glyph_id)
+}
+
+fn serve<P: AsRef<Path>>(path: P, port: u16) -> io::Result<()> {
+    let buffer = fs::read(path)?;
+    let scope = ReadScope::new(&buffer);
+    let font_file = scope.read::<FontFile<'_>>().map_err(to_io_error)?;
+    let table_provider = font_file.table_provider(0).map_err(to_io_error)?;
+    let svg_data = table_provider
+        .read_table_data(tag::SVG)
+        .map_err(to_io_error)?;
+    let svg = ReadScope::new(&svg_data)
+        .read::<SvgTable<'_>>()
+        .map_err(to_io_error)?;
+
+    let server = tiny_http::Server::http(("127.0.0.1", port)).map_err(to_io_error)?;
+    eprintln!("svg-dump: serving on http://127.0.0.1:{}", port);
+
+    let mut cache = PngCache::new(64);
+    for request in server.incoming_requests() {
+        if let Err(err) = handle_request(request, &svg, &mut cache) {
+            eprintln!("svg-dump: request error: {}", err);
+        }
     }
 
     Ok(())
 }
 
+fn handle_request(
+    request: tiny_http::Request,
+    svg: &SvgTable<'_>,
+    cache: &mut PngCache,
+) -> io::Result<()> {
+    let parse_start = Instant::now();
+    let glyph_request = parse_glyph_request(request.url());
+    let parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
+
+    let glyph_request = match glyph_request {
+        Some(glyph_request) => glyph_request,
+        None => return request.respond(tiny_http::Response::from_string("not found").with_status_code(404)),
+    };
+
+    match glyph_request {
+        GlyphRequest::Svg { glyph_id } => match find_record(svg, glyph_id) {
+            Some(record) => {
+                let svg_document = expand_document(record.svg_document)?;
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"image/svg+xml"[..])
+                        .unwrap();
+                request.respond(
+                    tiny_http::Response::from_string(svg_document).with_header(header),
+                )
+            }
+            None => request.respond(tiny_http::Response::from_string("not found").with_status_code(404)),
+        },
+        GlyphRequest::Png { glyph_id, size } => match find_record(svg, glyph_id) {
+            Some(record) => {
+                let render_start = Instant::now();
+                let (png, cache_hit) = cache.get_or_render(
+                    CacheKey { glyph_id, size },
+                    || {
+                        let svg_document = expand_document(record.svg_document)?;
+                        render_to_png(&svg_document, size).map_err(to_io_error)
+                    },
+                )?;
+                let render_ms = render_start.elapsed().as_secs_f64() * 1000.0;
+
+                let content_type =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"image/png"[..])
+                        .unwrap();
+                let server_timing = tiny_http::Header::from_bytes(
+                    &b"Server-Timing"[..],
+                    format!(
+                        "parse;dur={:.3}, render;dur={:.3}, cache;desc={}",
+                        parse_ms,
+                        render_ms,
+                        if cache_hit { "hit" } else { "miss" }
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+                request.respond(
+                    tiny_http::Response::from_data(png)
+                        .with_header(content_type)
+                        .with_header(server_timing),
+                )
+            }
+            None => request.respond(tiny_http::Response::from_string("not found").with_status_code(404)),
+        },
+    }
+}
+
 fn to_io_error<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> io::Error {
     io::Error::new(io::ErrorKind::Other, err)
 }
@@ -124,7 +666,96 @@ fn hexify(bytes: &[u8]) -> String {
     bytes
         .iter()
         .fold(String::with_capacity(bytes.len() * 2), |mut s, byte| {
-            write!(&mut s, "{:x}", byte).unwrap();
+            write!(&mut s, "{:02x}", byte).unwrap();
             s
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_glyph_request_svg() {
+        assert_eq!(
+            parse_glyph_request("/glyph/42.svg"),
+            Some(GlyphRequest::Svg { glyph_id: 42 })
+        );
+    }
+
+    #[test]
+    fn parse_glyph_request_png_default_size() {
+        assert_eq!(
+            parse_glyph_request("/glyph/7.png"),
+            Some(GlyphRequest::Png { glyph_id: 7, size: 128 })
+        );
+    }
+
+    #[test]
+    fn parse_glyph_request_png_explicit_size() {
+        assert_eq!(
+            parse_glyph_request("/glyph/7.png?size=256"),
+            Some(GlyphRequest::Png { glyph_id: 7, size: 256 })
+        );
+    }
+
+    #[test]
+    fn parse_glyph_request_rejects_unknown_paths() {
+        assert!(parse_glyph_request("/favicon.ico").is_none());
+        assert!(parse_glyph_request("/glyph/not-a-number.svg").is_none());
+    }
+
+    #[test]
+    fn char_spec_accepts_code_point_and_literal_char() {
+        assert_eq!(parse_char_spec("U+1F600").unwrap(), '\u{1F600}');
+        assert_eq!(parse_char_spec("A").unwrap(), 'A');
+        assert!(parse_char_spec("AB").is_err());
+    }
+
+    #[test]
+    fn looks_like_char_spec_distinguishes_flags_from_chars() {
+        assert!(looks_like_char_spec("U+FE0F"));
+        assert!(looks_like_char_spec("A"));
+        assert!(!looks_like_char_spec("--png"));
+    }
+
+    #[test]
+    fn parse_output_mode_xml_by_default() {
+        assert!(matches!(parse_output_mode(&[]).unwrap(), OutputMode::Xml));
+    }
+
+    #[test]
+    fn parse_output_mode_png() {
+        let args = vec!["--png".to_string(), "64".to_string(), "out.png".to_string()];
+        match parse_output_mode(&args).unwrap() {
+            OutputMode::Png { size: 64, destination } => {
+                assert_eq!(destination, PathBuf::from("out.png"))
+            }
+            _ => panic!("expected Png mode"),
+        }
+    }
+
+    #[test]
+    fn parse_output_mode_rejects_garbage() {
+        let args = vec!["--what".to_string()];
+        assert!(parse_output_mode(&args).is_err());
+    }
+
+    #[test]
+    fn png_cache_evicts_least_recently_used() {
+        let mut cache = PngCache::new(2);
+        let a = CacheKey { glyph_id: 1, size: 32 };
+        let b = CacheKey { glyph_id: 2, size: 32 };
+        let c = CacheKey { glyph_id: 3, size: 32 };
+
+        cache.get_or_render(a, || Ok(vec![1])).unwrap();
+        cache.get_or_render(b, || Ok(vec![2])).unwrap();
+        // Touch `a` so `b` becomes the least recently used entry.
+        cache.get_or_render(a, || panic!("should be cached")).unwrap();
+        cache.get_or_render(c, || Ok(vec![3])).unwrap();
+
+        assert!(!cache.entries.contains_key(&b));
+        assert!(cache.entries.contains_key(&a));
+        assert!(cache.entries.contains_key(&c));
+    }
+}